@@ -12,6 +12,13 @@ extern crate serde;
 extern crate serde_json;
 extern crate reqwest;
 extern crate isolang;
+extern crate futures;
+extern crate surf;
+extern crate indicatif;
+extern crate csv;
+extern crate rmp_serde;
+extern crate tide;
+extern crate async_std;
 
 #[macro_use]
 extern crate serde_derive;
@@ -26,18 +33,47 @@ extern crate maplit;
 extern crate lazy_static;
 
 use select::document::Document;
-use select::predicate::{Predicate, Class, Name};
+use select::predicate::{Predicate, Class, Name, Attr};
 use regex::Regex;
 use reqwest::Client;
 use isolang::Language;
+use futures::stream::{self, StreamExt};
+use indicatif::ProgressBar;
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::File;
 use std::error::Error;
 use std::io::Write;
 use std::io::BufWriter;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// Find the rankings table body, trying the dedicated "ranking" class
+// first and falling back to positional lookup (the first table on the
+// page) if that class has been renamed out from under us.
+fn find_ranking_tbody<'a>(document: &'a Document) -> Option<select::node::Node<'a>> {
+    document.find(Class("ranking").descendant(Name("tbody"))).next()
+        .or_else(|| document.find(Name("table").descendant(Name("tbody"))).next())
+}
+
+// Parse a single row of the rankings table. Returns Ok(None) for a row
+// that parsed fine but has no pages recorded (deliberately excluded, not
+// an error); returns Err if the row's markup doesn't look as expected.
+fn parse_ranking_row(trnode: &select::node::Node) -> Result<Option<String>, Box<Error>> {
+    let link = trnode.find(Name("a")).next().ok_or("ranking row has no user link")?;
+    let userurl = link.attr("href").ok_or("user link has no href")?;
+    let pagecount = trnode.find(Name("td")).nth(3).ok_or("ranking row is missing a page-count column")?.text();
+    let userid = userurl.split("/").last().ok_or("user link href has no path component")?;
+
+    // Note that this is a string comparison...
+    if pagecount == "0.0" {
+        return Ok(None);
+    }
+    Ok(Some(String::from(userid)))
+}
 
-fn parse_mainpage(document: Document) -> Vec<String> {
+fn parse_mainpage(document: Document) -> Result<Vec<String>, Box<Error>> {
     // Parse the top level rankings page, the relevant part of which looks like
     //	<table class="table">
     //   <thead> ... </thead>
@@ -55,131 +91,265 @@ fn parse_mainpage(document: Document) -> Vec<String> {
     // We just return a list of the IDs (we will get the username and score
     // info that we use from the individual user pages).
 
-    // For now our error handling is just to panic if we don't see what we expect.
+    let tablebody = find_ranking_tbody(&document).ok_or("could not find the rankings table")?;
 
     let mut users = Vec::new();
-
-    let tablebody = document.find(Class("ranking").descendant(Name("tbody"))).next().unwrap();
+    let mut skipped = 0;
     for trnode in tablebody.find(Name("tr")) {
-        let link = trnode.find(Name("a")).next().unwrap();
-        let userurl = link.attr("href").unwrap();
-        let pagecount = trnode.find(Name("td")).nth(3).unwrap().text();
-        let userid = userurl.split("/").last().unwrap();
-
-        // Note that this is a string comparison...
-        if pagecount != "0.0" {
-            //println!{"username {} userid {} pagecount {}", username, userid, pagecount};
-            users.push(String::from(userid));
+        match parse_ranking_row(&trnode) {
+            Ok(Some(userid)) => users.push(userid),
+            Ok(None) => (),
+            Err(e) => {
+                eprintln!{"Skipping malformed ranking row: {}", e};
+                skipped += 1;
+            }
         }
     }
-    return users;
+    if skipped > 0 {
+        eprintln!{"Rankings page: {} row(s) parsed, {} skipped as malformed", users.len(), skipped};
+    }
+    Ok(users)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// A single Highcharts daily series: the bare per-day values, plus enough
+// of the original pointStart/pointInterval to recover the date each
+// entry in `data` falls on (data[i] is the day at point_start + i *
+// point_interval, both in epoch milliseconds).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DatedSeries {
+    point_start: i64,
+    point_interval: i64,
+    data: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct UserInfo {
     name: String,
     countmap: HashMap<String, f64>,
-    seriesmap: HashMap<String, Vec<f64>>,
+    seriesmap: HashMap<String, DatedSeries>,
     totalpoints: f64,
 }
 
-fn parse_userpage(document: Document) -> UserInfo {
-    // We want to grab:
-    //  * the raw page counts for each category from the content tab
-    //  * the total point value
-    //  * the daily series data from the javascript
-    // TODO: maybe we should get the main page for each language instead?
-    let username = document.find(Class("avatar")).next().unwrap().attr("alt").unwrap();
+// The avatar's alt text is the normal place to find the username; if
+// that's gone missing, fall back to the page's <h1> heading.
+fn parse_username(document: &Document) -> Option<String> {
+    document.find(Class("avatar")).next()
+        .and_then(|tag| tag.attr("alt"))
+        .map(String::from)
+        .or_else(|| document.find(Name("h1")).next().map(|tag| tag.text().trim().to_string()))
+}
 
-    // Find the list of reading languages. This gets us a space-separated string
-    // with them all. (We only want it if there's just a single language.)
-    let langs = document.find(Class("info"))
+// Find the list of reading languages. This gets us a space-separated string
+// with them all. (We only want it if there's just a single language.)
+fn parse_langs(document: &Document) -> Option<String> {
+    document.find(Class("info"))
         .map(|tag| tag.text())
-        .filter(|t| t.starts_with("Reading language(s)"))
-        .next().unwrap()
-        .split_whitespace().skip(2).collect::<Vec<_>>().join(" ");
-
-    let tablehead = document.find(Class("table-bordered").descendant(Name("thead"))).next().unwrap();
-    let tablebody = document.find(Class("table-bordered").descendant(Name("tbody"))).next().unwrap();
-    // Pull the category names out of the table head. We discard the first <th> (empty)
-    // and the last ("Total")
+        .find(|t| t.starts_with("Reading language(s)"))
+        .map(|t| t.split_whitespace().skip(2).collect::<Vec<_>>().join(" "))
+}
+
+// Find the bordered stats table, trying the dedicated class first and
+// falling back to positional lookup (the first table on the page).
+fn find_stats_table<'a>(document: &'a Document) -> Option<select::node::Node<'a>> {
+    document.find(Class("table-bordered")).next()
+        .or_else(|| document.find(Name("table")).next())
+}
+
+// Pull the per-category raw counts and the total point value out of the
+// stats table. The first body row holds the raw counts, the second the
+// point totals, with the category names coming from the head row.
+fn parse_counts(table: &select::node::Node) -> Result<(HashMap<String, f64>, f64), Box<Error>> {
+    let tablehead = table.find(Name("thead")).next().ok_or("stats table has no head")?;
+    let tablebody = table.find(Name("tbody")).next().ok_or("stats table has no body")?;
+
+    // Discard the first <th> (empty) and the last ("Total").
     let headings = tablehead.find(Name("th"))
         .map(|tag| tag.text())
         .skip(1)
         .filter(|x| x != "Total")
         .collect::<Vec<_>>();
-    // First <tr> in here has the raw-page counts
-    let rawcounts = tablebody.find(Name("tr")).next().unwrap()
+
+    let rawcounts = tablebody.find(Name("tr")).next().ok_or("stats table has no raw-count row")?
         .find(Name("td"))
         .map(|tag| tag.text())
         .skip(1)
         .filter(|x| x != "")
-        .map(|x| x.parse::<f64>().unwrap())
-        .collect::<Vec<_>>();
-    // Create a category -> count hashtable
-    let countmap: HashMap<String, f64> =
-        headings.iter().cloned().zip(rawcounts).collect();
+        .map(|x| x.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()?;
 
-    // Now get the total point value out of the 2nd <tr>
-    let totalpoints = tablebody.find(Name("tr")).nth(1).unwrap()
+    let countmap: HashMap<String, f64> = headings.into_iter().zip(rawcounts).collect();
+
+    let totalpoints = tablebody.find(Name("tr")).nth(1).ok_or("stats table has no totals row")?
         .find(Name("td"))
-        .last().unwrap()
+        .last().ok_or("totals row has no columns")?
         .text()
-        .parse().unwrap();
+        .parse()?;
 
-    let js = document.find(Name("script"))
-        .map(|tag| tag.text())
-        .filter(|t| t.contains("progress_chart"))
-        .next().unwrap();
-
-    // Within the JS nodes we have to fish stuff out by regex.
-    // Firstly, if the text doesn't include "progress_chart"
-    // it's the wrong script node.
-
-    // We're looking for a bit of js like this:
-    //   series: [{
-    //    name: "Overall",
-    //    pointInterval: 86400000,
-    //    pointStart: 1506816000000,
-    //    data: [294.20000000000005, 0, 8.0, 57.6, 77.6, 88.00000000000001, 68.0, 45.5, 0]
-    //   }, {
-    //    name: "jp",
-    //    pointInterval: 86400000,
-    //    pointStart: 1506816000000,
-    //    data: [285.20000000000005, 0, 0, 51.6, 77.6, 83.00000000000001, 41.0, 21.0, 0]
-    //   }]
-    // which has one entry for Overall and one for each language. We assume the
-    // info is always per-day and just go for the data arrays.
+    Ok((countmap, totalpoints))
+}
+
+// Find the <script> node holding the Highcharts series data. Tadoku names
+// this chart "progress_chart"; fall back to any script that looks like it
+// defines a Highcharts series in case that name changes.
+fn find_series_script(document: &Document) -> Option<String> {
+    document.find(Name("script")).map(|tag| tag.text())
+        .find(|t| t.contains("progress_chart"))
+        .or_else(|| document.find(Name("script")).map(|tag| tag.text()).find(|t| t.contains("series:")))
+}
+
+// Within the JS node we have to fish stuff out by regex.
+// We're looking for a bit of js like this:
+//   series: [{
+//    name: "Overall",
+//    pointInterval: 86400000,
+//    pointStart: 1506816000000,
+//    data: [294.20000000000005, 0, 8.0, 57.6, 77.6, 88.00000000000001, 68.0, 45.5, 0]
+//   }, {
+//    name: "jp",
+//    pointInterval: 86400000,
+//    pointStart: 1506816000000,
+//    data: [285.20000000000005, 0, 0, 51.6, 77.6, 83.00000000000001, 41.0, 21.0, 0]
+//   }]
+// which has one entry for Overall and one for each language. We take the
+// data array of each series along with its pointStart and pointInterval,
+// so each entry's date can be recovered later.
+fn parse_series(js: &str) -> Result<Vec<(String, DatedSeries)>, Box<Error>> {
     let seriesnames = Regex::new(r#"name: "([^"]*)""#).unwrap()
-        .captures_iter(&*js)
+        .captures_iter(js)
         .map(|caps| caps[1].to_string())
         .collect::<Vec<_>>();
 
     let dataarrays = Regex::new(r#"data: \[([^\]]*)\]"#).unwrap()
-        .captures_iter(&*js)
-        .map(|caps| caps[1].to_string())
-        .map(|ds| ds.split(",")
-             .map(|s| s.trim().parse::<f64>().unwrap())
-             .collect::<Vec<_>>())
-        .collect::<Vec<_>>();
+        .captures_iter(js)
+        .map(|caps| caps[1].to_string()
+             .split(",")
+             .map(|s| s.trim().parse::<f64>())
+             .collect::<Result<Vec<_>, _>>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let pointstarts = Regex::new(r#"pointStart: (\d+)"#).unwrap()
+        .captures_iter(js)
+        .map(|caps| caps[1].parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let pointintervals = Regex::new(r#"pointInterval: (\d+)"#).unwrap()
+        .captures_iter(js)
+        .map(|caps| caps[1].parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if seriesnames.len() != dataarrays.len() || seriesnames.len() != pointstarts.len()
+        || seriesnames.len() != pointintervals.len() {
+        return Err("series name/data/pointStart/pointInterval counts don't match".into());
+    }
+
+    Ok(seriesnames.into_iter()
+        .zip(dataarrays.into_iter().zip(pointstarts.into_iter().zip(pointintervals.into_iter())))
+        .map(|(name, (data, (point_start, point_interval)))|
+             (name, DatedSeries { point_start, point_interval, data }))
+        .collect())
+}
+
+fn parse_userpage(document: Document) -> Result<UserInfo, Box<Error>> {
+    // We want to grab:
+    //  * the raw page counts for each category from the content tab
+    //  * the total point value
+    //  * the daily series data from the javascript
+    // TODO: maybe we should get the main page for each language instead?
+    let username = parse_username(&document).ok_or("could not find username")?;
+    let langs = parse_langs(&document).ok_or("could not find reading languages")?;
+
+    let table = find_stats_table(&document).ok_or("could not find stats table")?;
+    let (countmap, totalpoints) = parse_counts(&table)?;
 
-    let entry_0_copy = dataarrays[0].clone();
+    let js = find_series_script(&document).ok_or("could not find progress chart script")?;
+    let series = parse_series(&js)?;
+    let numseries = series.len();
+    let entry_0_copy = series.first().ok_or("progress chart has no series")?.1.clone();
 
-    let mut seriesmap: HashMap<String, Vec<f64>> =
-        seriesnames.iter().cloned().zip(dataarrays).collect();
+    let mut seriesmap: HashMap<String, DatedSeries> = series.into_iter().collect();
 
-    if seriesnames.len() == 1 {
+    if numseries == 1 {
         // Single-language user, add an entry for "lang" as well as the
         // "Overall" one.
         seriesmap.insert(langs, entry_0_copy);
     }
 
-    UserInfo {
-        name : String::from(username),
+    Ok(UserInfo {
+        name: username,
         countmap: countmap,
         seriesmap: seriesmap,
-        totalpoints : totalpoints,
+        totalpoints: totalpoints,
+    })
+}
+
+// Epoch-milliseconds timestamp of the day at index `idx` within `series`.
+fn series_date(series: &DatedSeries, idx: usize) -> i64 {
+    series.point_start + (idx as i64) * series.point_interval
+}
+
+// Longest run of consecutive days with a nonzero value. Trailing zero
+// entries for days the contest hasn't reached yet don't form part of
+// any streak, so no special-casing is needed for them here.
+fn longest_streak(series: &DatedSeries) -> usize {
+    let mut best = 0;
+    let mut current = 0;
+    for &v in &series.data {
+        if v > 0.0 {
+            current += 1;
+            best = best.max(current);
+        } else {
+            current = 0;
+        }
     }
+    best
+}
+
+// The single best day in the series, as (date, value).
+fn best_day(series: &DatedSeries) -> Option<(i64, f64)> {
+    series.data.iter().enumerate()
+        .max_by(|&(_, a), &(_, b)| a.partial_cmp(b).unwrap())
+        .map(|(idx, &v)| (series_date(series, idx), v))
+}
+
+// Sum the series into 7-day buckets aligned to point_start, returned as
+// (bucket start date, total) pairs in chronological order.
+fn weekly_totals(series: &DatedSeries) -> Vec<(i64, f64)> {
+    let mut weeks: HashMap<i64, f64> = HashMap::new();
+    let week_ms = 7 * series.point_interval;
+    for (idx, &v) in series.data.iter().enumerate() {
+        let week_start = series.point_start + (idx as i64 / 7) * week_ms;
+        *weeks.entry(week_start).or_insert(0.0) += v;
+    }
+    let mut out = weeks.into_iter().collect::<Vec<_>>();
+    out.sort_by_key(|&(week_start, _)| week_start);
+    out
+}
+
+// The single most active week, as (week start date, total).
+fn most_active_week(series: &DatedSeries) -> Option<(i64, f64)> {
+    weekly_totals(series).into_iter()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+}
+
+// Render an epoch-milliseconds timestamp (as used by point_start/the
+// dates above) as a plain "YYYY-MM-DD" string, so "Best single day" and
+// "Most active week" can show *when*, not just how much. This is Howard
+// Hinnant's civil_from_days algorithm (proleptic Gregorian calendar);
+// we do the date math by hand rather than pull in a whole date/time
+// crate for one field.
+fn epoch_ms_to_date(ms: i64) -> String {
+    let days = ms.div_euclid(86_400_000);
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = if m <= 2 { y + 1 } else { y };
+    format!("{:04}-{:02}-{:02}", y, m, d)
 }
 
 // NB that serializing and deserializing can make tiny rounding errors
@@ -199,32 +369,271 @@ fn read_json(file: &File) -> Result<Vec<UserInfo>, Box<Error>> {
     Ok(users)
 }
 
-fn doc_from_url(client: &Client, url: &str) -> Result<Document, Box<Error>> {
-    eprintln!{"Fetching page {}...", url};
-    let d = Document::from_read(client.get(url).send()?)?;
-    Ok(d)
+// The set of cookies we're carrying, as a simple name->value map. This is
+// serialized to disk as-is between runs rather than trying to preserve
+// full cookie semantics (domain/path/expiry) -- Tadoku only sets the one
+// session cookie we care about, so that's not worth the complexity.
+struct CookieStorage {
+    path: Option<String>,
+    cookies: HashMap<String, String>,
 }
 
-fn read_from_webpage() -> Result<Vec<UserInfo>, Box<Error>> {
-    let mut users = Vec::new();
-    let client = Client::new();
+impl CookieStorage {
+    fn load(path: Option<&str>) -> Result<CookieStorage, Box<Error>> {
+        let cookies = match path {
+            Some(p) if Path::new(p).exists() => serde_json::from_reader(File::open(p)?)?,
+            _ => HashMap::new(),
+        };
+        Ok(CookieStorage { path: path.map(String::from), cookies })
+    }
 
-    let mainpage = doc_from_url(&client, "http://readmod.com/ranking")?;
-    eprintln!{"Parsing frontpage..."};
-    let userids = parse_mainpage(mainpage);
-    for uid in userids {
-        let userpage = doc_from_url(&client, &("http://readmod.com/users/".to_string() + &uid))?;
-        eprintln!{"Parsing user page..."};
-        users.push(parse_userpage(userpage));
+    fn header_value(&self) -> Option<String> {
+        if self.cookies.is_empty() {
+            return None;
+        }
+        Some(self.cookies.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("; "))
+    }
+
+    fn update_from_headers(&mut self, headers: &reqwest::header::HeaderMap) {
+        for raw in headers.get_all(reqwest::header::SET_COOKIE) {
+            if let Ok(s) = raw.to_str() {
+                if let Some(kv) = s.split(';').next() {
+                    if let Some(eq) = kv.find('=') {
+                        self.cookies.insert(kv[..eq].to_string(), kv[eq + 1..].to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    fn persist(&self) -> Result<(), Box<Error>> {
+        if let Some(ref path) = self.path {
+            serde_json::to_writer(File::create(path)?, &self.cookies)?;
+        }
+        Ok(())
+    }
+}
+
+// A keep-alive session that carries cookies across the ranking page and
+// every user page, and can persist them to disk so a login only has to
+// happen once across runs. Modelled on snowchains_core's split between a
+// CookieStorage (just the jar) and a Session (the client that uses it).
+struct Session {
+    client: Client,
+    jar: CookieStorage,
+}
+
+impl Session {
+    fn new(cookie_path: Option<&str>) -> Result<Session, Box<Error>> {
+        Ok(Session { client: Client::new(), jar: CookieStorage::load(cookie_path)? })
+    }
+
+    fn get(&mut self, url: &str) -> Result<Document, Box<Error>> {
+        eprintln!{"Fetching page {}...", url};
+        let mut req = self.client.get(url);
+        if let Some(cookie) = self.jar.header_value() {
+            req = req.header(reqwest::header::COOKIE, cookie);
+        }
+        let resp = req.send()?;
+        self.jar.update_from_headers(resp.headers());
+        Ok(Document::from_read(resp)?)
+    }
+
+    // POST credentials to the site's login form and pick up the resulting
+    // session cookie. Tadoku's login form embeds a CSRF token, so we have
+    // to fetch the form first and round-trip that along with the password.
+    fn login(&mut self, username: &str, password: &str) -> Result<(), Box<Error>> {
+        eprintln!{"Logging in as {}...", username};
+        let form = self.get("http://readmod.com/users/sign_in")?;
+        let csrf = form.find(Name("meta").and(Attr("name", "csrf-token")))
+            .next().and_then(|tag| tag.attr("content"))
+            .unwrap_or("").to_string();
+        let params = [
+            ("user[email]", username),
+            ("user[password]", password),
+            ("authenticity_token", csrf.as_str()),
+        ];
+        let mut req = self.client.post("http://readmod.com/users/sign_in").form(&params);
+        if let Some(cookie) = self.jar.header_value() {
+            req = req.header(reqwest::header::COOKIE, cookie);
+        }
+        let resp = req.send()?;
+        self.jar.update_from_headers(resp.headers());
+        Ok(())
+    }
+
+    fn cookie_header(&self) -> Option<String> {
+        self.jar.header_value()
+    }
+
+    fn persist_cookies(&self) -> Result<(), Box<Error>> {
+        self.jar.persist()
     }
-    Ok(users)
 }
 
-type ResultTable<'a> = Vec<(&'a String, f64)>;
+// A DataSource knows how to obtain the full set of UserInfo records for
+// the current contest. We have two implementations: the original one
+// that scrapes the rendered HTML pages, and a newer one that talks to
+// the official JSON API. Keeping this behind a trait means the rest of
+// the program (and --readjson/--writejson) doesn't care which one was used.
+// Send + Sync so a DataSource can be shared with --serve's HTTP handlers.
+trait DataSource: Send + Sync {
+    fn fetch_users(&self) -> Result<Vec<UserInfo>, Box<Error>>;
+}
+
+struct HtmlSource {
+    concurrency: usize,
+    cookie_path: Option<String>,
+    credentials: Option<(String, String)>,
+}
+
+impl HtmlSource {
+    fn new(concurrency: usize, cookie_path: Option<String>, credentials: Option<(String, String)>) -> HtmlSource {
+        // buffer_unordered(0) admits no futures and never makes progress,
+        // so a --concurrency 0 would otherwise hang forever instead of
+        // fetching anything.
+        HtmlSource { concurrency: concurrency.max(1), cookie_path, credentials }
+    }
+}
+
+impl DataSource for HtmlSource {
+    fn fetch_users(&self) -> Result<Vec<UserInfo>, Box<Error>> {
+        let mut session = Session::new(self.cookie_path.as_ref().map(String::as_str))?;
+
+        match self.credentials {
+            Some((ref username, ref password)) => session.login(username, password)?,
+            None => eprintln!{"No --login credentials supplied, scraping anonymously..."},
+        }
+
+        let mainpage = session.get("http://readmod.com/ranking")?;
+        eprintln!{"Parsing frontpage..."};
+        let userids = parse_mainpage(mainpage)?;
+
+        // Carry over whichever cookie the session ended up with (anonymous
+        // or logged in) so member-only user pages are reachable here too.
+        let cookie = session.cookie_header();
+
+        // Fetch up to `concurrency` user pages at once instead of one at a
+        // time: with hundreds of contestants the sequential version spends
+        // almost all its time waiting on the network. We drive this with
+        // surf/async-std rather than reqwest's async client/tokio so that
+        // --serve's tide handlers (which are async-std all the way down,
+        // and call back into fetch_users() for /api/refresh) only ever
+        // have one executor to deal with: unlike tokio, async-std's
+        // block_on doesn't mind being entered again from inside a task
+        // that's already running under it.
+        let progress = ProgressBar::new(userids.len() as u64);
+
+        // One shared client so all the concurrent fetches reuse its
+        // connection pool (surf::Client clones are cheap handles onto the
+        // same pool) instead of paying a fresh TCP+TLS handshake per user.
+        let client = surf::Client::new();
+
+        let fetches = stream::iter(userids.clone())
+            .map(|uid| {
+                let url = "http://readmod.com/users/".to_string() + &uid;
+                let cookie = cookie.clone();
+                let progress = progress.clone();
+                let client = client.clone();
+                async move {
+                    let mut req = client.get(&url);
+                    if let Some(cookie) = cookie {
+                        req = req.header("Cookie", cookie);
+                    }
+                    // A malformed individual user page (or a failed fetch)
+                    // shouldn't abort the whole scrape: log it and skip
+                    // that one user.
+                    let user = match req.recv_string().await {
+                        Ok(text) => match parse_userpage(Document::from(text.as_str())) {
+                            Ok(user) => Some(user),
+                            Err(e) => {
+                                eprintln!{"Skipping user {}: {}", uid, e};
+                                None
+                            }
+                        },
+                        Err(e) => {
+                            eprintln!{"Skipping user {}: {}", uid, e};
+                            None
+                        }
+                    };
+                    progress.inc(1);
+                    (uid, user)
+                }
+            })
+            .buffer_unordered(self.concurrency);
+
+        let mut byid: HashMap<String, UserInfo> =
+            async_std::task::block_on(fetches.collect::<Vec<_>>())
+            .into_iter().filter_map(|(uid, user)| user.map(|u| (uid, u)))
+            .collect();
+        progress.finish();
+
+        session.persist_cookies()?;
+
+        let skipped = userids.len() - byid.len();
+        eprintln!{"Parsed {} user page(s) cleanly, {} skipped as malformed or not fetched", byid.len(), skipped};
+
+        // Collecting into a hashmap means completion order (which varies
+        // run to run with concurrent fetches) doesn't leak into our output;
+        // rebuild the list in the ranking page's original order.
+        let users = userids.into_iter().filter_map(|uid| byid.remove(&uid)).collect();
+        Ok(users)
+    }
+}
+
+// Shape of the records returned by the official JSON API. This is
+// deliberately a separate type from UserInfo: the API's field names
+// and units don't exactly match what we store internally, and keeping
+// them distinct means a future API revision only requires changing
+// ApiUserInfo and its From impl rather than our serialized UserInfo
+// format (which --readjson/--writejson depend on).
+#[derive(Debug, Serialize, Deserialize)]
+struct ApiUserInfo {
+    username: String,
+    counts: HashMap<String, f64>,
+    series: HashMap<String, DatedSeries>,
+    total: f64,
+}
+
+impl From<ApiUserInfo> for UserInfo {
+    fn from(api: ApiUserInfo) -> UserInfo {
+        UserInfo {
+            name: api.username,
+            countmap: api.counts,
+            seriesmap: api.series,
+            totalpoints: api.total,
+        }
+    }
+}
+
+struct ApiSource;
+
+impl DataSource for ApiSource {
+    fn fetch_users(&self) -> Result<Vec<UserInfo>, Box<Error>> {
+        let client = Client::new();
+        eprintln!{"Fetching user data from API..."};
+        let apiusers: Vec<ApiUserInfo> =
+            client.get("http://readmod.com/api/v1/users").send()?.json()?;
+        Ok(apiusers.into_iter().map(UserInfo::from).collect())
+    }
+}
+
+fn datasource_from_name(name: &str, concurrency: usize, cookie_path: Option<String>,
+                         credentials: Option<(String, String)>) -> Box<DataSource> {
+    match name {
+        "api" => Box::new(ApiSource),
+        _ => Box::new(HtmlSource::new(concurrency, cookie_path, credentials)),
+    }
+}
+
+// The name column is a Cow rather than a plain &str reference because
+// most tables just borrow a user's name, but a few (see get_dated_table)
+// need to build an owned "name (date)" label instead.
+type ResultTable<'a> = Vec<(Cow<'a, str>, f64)>;
 
 fn get_table<F>(users: &Vec<UserInfo>, maxentries: usize, keyfn: F) -> ResultTable
     where F: Fn(&UserInfo) -> f64
-{ 
+{
     // Sort this vector of integers according to the keyfn, taking account of
     // the difficulties with sorting f64s.
     let mut usridx = (0..users.len()).collect::<Vec<_>>();
@@ -238,11 +647,35 @@ fn get_table<F>(users: &Vec<UserInfo>, maxentries: usize, keyfn: F) -> ResultTab
     let mut tablevec = Vec::new();
 
     for u in usridx {
-        tablevec.push((&users[u].name, getv(&u)));
+        tablevec.push((Cow::Borrowed(users[u].name.as_str()), getv(&u)));
     }
     tablevec
 }
 
+// Like get_table, but for rankings that are really "a day/week and what
+// happened on it" rather than a bare score: ResultTable has no separate
+// date column, so we fold the date into the display name instead of
+// discarding it the way a plain get_table(..., |u| ...value-only) would.
+fn get_dated_table<F>(users: &Vec<UserInfo>, maxentries: usize, datefn: F) -> ResultTable
+    where F: Fn(&UserInfo) -> Option<(i64, f64)>
+{
+    let mut usridx = (0..users.len()).collect::<Vec<_>>();
+    let getv = |x: &usize| datefn(&users[*x]).map_or(0.0, |(_, v)| v);
+    usridx.sort_unstable_by(|a, b| getv(b).partial_cmp(&(getv(a))).unwrap());
+    if maxentries != 0 {
+        usridx.truncate(maxentries);
+    }
+    usridx.retain(|x| getv(x) >= 0.01);
+
+    usridx.into_iter()
+        .map(|u| {
+            let (date, value) = datefn(&users[u]).unwrap();
+            let name = format!("{} ({})", users[u].name, epoch_ms_to_date(date));
+            (Cow::Owned(name), value)
+        })
+        .collect()
+}
+
 fn print_table<W: Write>(ds: &mut BufWriter<W>, title: &str, table: &ResultTable, html: bool) {
     if html {
         write!(ds, "<p><h5>{}</h5>\n<p>\n", title).unwrap();
@@ -251,8 +684,8 @@ fn print_table<W: Write>(ds: &mut BufWriter<W>, title: &str, table: &ResultTable
     }
     let brtag = if html { "<br />" } else { "" };
 
-    for (i, &(name, value)) in table.iter().enumerate() {
-        write!(ds, "{}. {} {:.2}{}\n", i + 1, name, value, brtag).unwrap();
+    for (i, entry) in table.iter().enumerate() {
+        write!(ds, "{}. {} {:.2}{}\n", i + 1, entry.0, entry.1, brtag).unwrap();
     }
     if html {
         write!(ds, "</p>\n").unwrap();
@@ -310,69 +743,21 @@ lazy_static! {
         "Sentences" => "sentences read",
         "Subs" => "minutes of subs watched",
     };
-    static ref MEDIUM_ACTOR: HashMap<&'static str, &'static str> = hashmap!{
-        "Book" => "book reader",
-        "Full Game" => "full-game reader",
-        "Game" => "game reader",
-        "Lyrics" => "lyric reader",
-        "Manga" => "manga reader",
-        "Net" => "net reader",
-        "News" => "news reader",
-        "Nico" => "nico reader/watcher",
-        "Sentences" => "sentence reader",
-        "Subs" => "subs reader/watcher",
-    };
-    static ref MEDIUM_UNITS: HashMap<&'static str, &'static str> = hashmap!{
-        "Book" => "pages",
-        "Full Game" => "screens",
-        "Game" => "screens",
-        "Lyrics" => "lyrics",
-        "Manga" => "pages",
-        "Net" => "pages",
-        "News" => "articles",
-        "Nico" => "nico",
-        "Sentences" => "sentences",
-        "Subs" => "minutes",
-    };
 }
 
-// The default cases could be prettier if we incorporated the medium name,
-// but in practice they'll never be used so it's not worth the effort.
+// The default case could be prettier if we incorporated the medium name,
+// but in practice it'll never be used so it's not worth the effort.
 fn medium_description(m: &str) -> &'static str {
     MEDIUM_DESCRIPTION.get(m).unwrap_or(&"raw counts")
 }
 
-fn medium_actor(m: &str) -> &'static str {
-    MEDIUM_ACTOR.get(m).unwrap_or(&"thing reader")
-}
-
-fn medium_units(m: &str) -> &'static str {
-    MEDIUM_UNITS.get(m).unwrap_or(&"raw units")
-}
-
-fn print_brief_medium_table<W: Write>(ds: &mut BufWriter<W>, m: &str, table: &ResultTable, html: bool) {
-    // Just print the top two contenders for the medium, in a
-    // conversational format.
-    // For HTML we print the second one as a list nested inside the first,
-    // which typically makes it render as indented.
-    let ulli = if html { "<ul><li>" } else { "" };
-    let closeulli = if html { "</ul></li>" } else { "</ul></li>" };
-    match table.get(1) {
-        Some(&(name, value)) =>
-            write!(ds, "{}{} is our top {} with {} {}.\n", ulli,
-                   name, medium_actor(m), value, medium_description(m)).unwrap(),
-        None => return,
-    };
-    match table.get(2) {
-        Some(&(name, value)) =>
-            write!(ds, "{}Honorable mention goes to {} with {} {} recorded.\n{}{}\n",
-                   ulli, name, value, medium_units(m), closeulli, closeulli).unwrap(),
-        None => write!(ds, "{}\n", closeulli).unwrap(),
-    };
-}
-
-fn print_stats(dest: Box<Write>, users: &Vec<UserInfo>, brief: bool, html: bool) {
-    let mut ds = BufWriter::new(dest);
+// Build the full set of ranking tables we report on: overall points,
+// one table per medium, one table per language, and the longest streak,
+// best single day and most active week tables. This is the data
+// that every output format renders, so it's computed once up front
+// rather than inside each formatter.
+fn build_tables<'a>(users: &'a Vec<UserInfo>) -> Vec<(String, ResultTable<'a>)> {
+    let mut tables = Vec::new();
 
     let mut media = users.iter()
         .flat_map(|u| u.countmap.keys())
@@ -387,59 +772,285 @@ fn print_stats(dest: Box<Write>, users: &Vec<UserInfo>, brief: bool, html: bool)
     languages.sort_by(lang_comparator);
     languages.dedup();
 
-    {
-        let table = get_table(&users, 0, |u| u.totalpoints);
-        print_table(&mut ds, "Overall rankings", &table, html);
-    }
-
-    if html {
-        write!(ds, "<h4>MEDIUM CHAMPS</h4>\n\n").unwrap();
-    }
+    tables.push((String::from("Overall rankings"), get_table(&users, 0, |u| u.totalpoints)));
 
     for m in media {
         let table = get_table(&users, 3, |u| *u.countmap.get(m).unwrap_or(&0.0));
         if table.len() == 0 {
             continue;
         }
-
-        if brief {
-            print_brief_medium_table(&mut ds, m, &table, html);
-        } else {
-            let title = format!("{} rankings ({})", m, medium_description(m));
-            print_table(&mut ds, &title, &table, html);
-        }
+        let title = format!("{} rankings ({})", m, medium_description(m));
+        tables.push((title, table));
     }
 
     for l in languages {
-        let emptyvec : Vec<f64> = Vec::new();
         let table = get_table(&users, 10,
-                              |u| u.seriesmap.get(l).unwrap_or(&emptyvec).iter()
-                              .fold(0.0, |sum, x| sum + x));
+                              |u| u.seriesmap.get(l).map_or(0.0, |s| s.data.iter()
+                              .fold(0.0, |sum, x| sum + x)));
         if table.len() == 0 {
             continue;
         }
         let title = lang_table_title(l, &table);
-        print_table(&mut ds, &title, &table, html);
+        tables.push((title, table));
+    }
+
+    // These three use each user's "Overall" series rather than iterating
+    // every language they read in, since single-language users have their
+    // one real language's series duplicated under the language key as
+    // well as under "Overall" (see parse_userpage) -- keying off "Overall"
+    // avoids counting such a user's activity twice.
+    tables.push((String::from("Longest streak"), get_table(&users, 10,
+        |u| u.seriesmap.get("Overall").map_or(0.0, |s| longest_streak(s) as f64))));
+    // Best single day/most active week are dated, not just a bare score,
+    // so they go through get_dated_table to show *when* alongside *how much*.
+    tables.push((String::from("Best single day"), get_dated_table(&users, 10,
+        |u| u.seriesmap.get("Overall").and_then(best_day))));
+    tables.push((String::from("Most active week"), get_dated_table(&users, 10,
+        |u| u.seriesmap.get("Overall").and_then(most_active_week))));
+
+    tables
+}
+
+// Output formats all render the same Vec<(title, ResultTable)> produced by
+// build_tables(), so adding a new format (as ilc does for its message
+// loggers) only means adding a new impl of this trait.
+trait StatsFormatter {
+    fn format(&self, dest: Box<Write>, tables: &[(String, ResultTable)]) -> Result<(), Box<Error>>;
+}
+
+struct TextFormatter;
+
+impl StatsFormatter for TextFormatter {
+    fn format(&self, dest: Box<Write>, tables: &[(String, ResultTable)]) -> Result<(), Box<Error>> {
+        let mut ds = BufWriter::new(dest);
+        for &(ref title, ref table) in tables {
+            print_table(&mut ds, title, table, false);
+        }
+        Ok(())
+    }
+}
+
+struct HtmlFormatter;
+
+impl StatsFormatter for HtmlFormatter {
+    fn format(&self, dest: Box<Write>, tables: &[(String, ResultTable)]) -> Result<(), Box<Error>> {
+        let mut ds = BufWriter::new(dest);
+        for &(ref title, ref table) in tables {
+            print_table(&mut ds, title, table, true);
+        }
+        Ok(())
+    }
+}
+
+struct CsvFormatter;
+
+impl StatsFormatter for CsvFormatter {
+    fn format(&self, dest: Box<Write>, tables: &[(String, ResultTable)]) -> Result<(), Box<Error>> {
+        let mut w = csv::Writer::from_writer(dest);
+        w.write_record(&["table", "rank", "name", "value"])?;
+        for &(ref title, ref table) in tables {
+            for (i, entry) in table.iter().enumerate() {
+                w.write_record(&[title.clone(), (i + 1).to_string(), entry.0.to_string(), entry.1.to_string()])?;
+            }
+        }
+        w.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct RankingEntry {
+    rank: usize,
+    name: String,
+    value: f64,
+}
+
+#[derive(Serialize)]
+struct RankingTable {
+    title: String,
+    entries: Vec<RankingEntry>,
+}
+
+fn ranking_tables(tables: &[(String, ResultTable)]) -> Vec<RankingTable> {
+    tables.iter().map(|&(ref title, ref table)| {
+        let entries = table.iter().enumerate()
+            .map(|(i, entry)| RankingEntry { rank: i + 1, name: entry.0.to_string(), value: entry.1 })
+            .collect();
+        RankingTable { title: title.clone(), entries }
+    }).collect()
+}
+
+struct JsonFormatter;
+
+impl StatsFormatter for JsonFormatter {
+    fn format(&self, dest: Box<Write>, tables: &[(String, ResultTable)]) -> Result<(), Box<Error>> {
+        serde_json::to_writer(dest, &ranking_tables(tables))?;
+        Ok(())
+    }
+}
+
+struct MsgpackFormatter;
+
+impl StatsFormatter for MsgpackFormatter {
+    fn format(&self, mut dest: Box<Write>, tables: &[(String, ResultTable)]) -> Result<(), Box<Error>> {
+        let bytes = rmp_serde::to_vec(&ranking_tables(tables))?;
+        dest.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+fn formatter_from_name(name: &str) -> Box<StatsFormatter> {
+    match name {
+        "html" => Box::new(HtmlFormatter),
+        "csv" => Box::new(CsvFormatter),
+        "json" => Box::new(JsonFormatter),
+        "msgpack" => Box::new(MsgpackFormatter),
+        _ => Box::new(TextFormatter),
+    }
+}
+
+// A Write adapter that lets a StatsFormatter (which wants to own its
+// Box<Write> destination) render into an in-memory buffer we can read
+// back afterwards, for serving out of --serve.
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn render_html(users: &Vec<UserInfo>) -> Result<String, Box<Error>> {
+    let tables = build_tables(users);
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    HtmlFormatter.format(Box::new(SharedBuf(buf.clone())), &tables)?;
+    let bytes = Arc::try_unwrap(buf).unwrap().into_inner().unwrap();
+    Ok(String::from_utf8(bytes)?)
+}
+
+// State shared between all of --serve's HTTP handlers: the current
+// snapshot of scraped users, and the DataSource that can fetch a new one
+// for the refresh endpoint.
+#[derive(Clone)]
+struct ServerState {
+    users: Arc<Mutex<Vec<UserInfo>>>,
+    source: Arc<DataSource>,
+}
+
+async fn index_route(req: tide::Request<ServerState>) -> tide::Result {
+    let users = req.state().users.lock().unwrap().clone();
+    let html = render_html(&users).map_err(|e| tide::Error::from_str(500, e.to_string()))?;
+    Ok(tide::Response::builder(200)
+        .body(html)
+        .content_type(tide::http::mime::HTML)
+        .build())
+}
+
+async fn stats_json_route(req: tide::Request<ServerState>) -> tide::Result {
+    let users = req.state().users.lock().unwrap().clone();
+    let tables = build_tables(&users);
+    let body = serde_json::to_string(&ranking_tables(&tables))
+        .map_err(|e| tide::Error::from_str(500, e.to_string()))?;
+    Ok(tide::Response::builder(200)
+        .body(body)
+        .content_type(tide::http::mime::JSON)
+        .build())
+}
+
+// Looked up by display name, not a numeric site id: not every DataSource
+// has one (ApiSource's ApiUserInfo is keyed by username already), so
+// UserInfo never carries one and the route parameter is named to match.
+async fn user_route(req: tide::Request<ServerState>) -> tide::Result {
+    let name = req.param("name")?.to_string();
+    let users = req.state().users.lock().unwrap();
+    match users.iter().find(|u| u.name == name) {
+        Some(user) => {
+            let body = serde_json::to_string(user)
+                .map_err(|e| tide::Error::from_str(500, e.to_string()))?;
+            Ok(tide::Response::builder(200)
+                .body(body)
+                .content_type(tide::http::mime::JSON)
+                .build())
+        }
+        None => Ok(tide::Response::builder(404).build()),
     }
 }
 
+async fn refresh_route(req: tide::Request<ServerState>) -> tide::Result {
+    eprintln!{"Refreshing stats..."};
+    // fetch_users() does blocking network I/O (and, for HtmlSource, its
+    // own nested async_std::task::block_on). A refresh can take minutes
+    // for a big contest, so running it inline here would tie up one of
+    // tide's executor threads and stall every other request on this
+    // "live" leaderboard for the duration -- run it on async-std's
+    // blocking thread pool instead and swap the snapshot in once it
+    // completes. Box<dyn Error> isn't Send, so stringify the error
+    // before it crosses the spawn_blocking boundary.
+    let source = req.state().source.clone();
+    let fresh: Result<Vec<UserInfo>, String> = async_std::task::spawn_blocking(move || {
+        source.fetch_users().map_err(|e| e.to_string())
+    }).await;
+    let fresh = fresh.map_err(|e| tide::Error::from_str(500, e))?;
+    *req.state().users.lock().unwrap() = fresh;
+    Ok(tide::Response::new(204))
+}
+
+fn serve(addr: &str, users: Vec<UserInfo>, source: Box<DataSource>) -> Result<(), Box<Error>> {
+    let state = ServerState {
+        users: Arc::new(Mutex::new(users)),
+        source: Arc::from(source),
+    };
+    let mut app = tide::with_state(state);
+    app.at("/").get(index_route);
+    app.at("/api/stats.json").get(stats_json_route);
+    app.at("/api/user/:name").get(user_route);
+    app.at("/api/refresh").post(refresh_route);
+    eprintln!{"Serving live stats on {}...", addr};
+    async_std::task::block_on(app.listen(addr))?;
+    Ok(())
+}
+
 fn main() {
     let matches = clap_app!(tadoku_stats =>
                             (version: crate_version!())
                             (author: crate_authors!())
                             (about: "Print summary statistics for Tadoku contest")
                             (@arg readjson: --readjson [JSONFILE] "Read data from json file rather than the website")
+                            (@arg source: --source [SOURCE] possible_values(&["html", "api"]) default_value("html") "Data source to scrape when not using --readjson")
+                            (@arg concurrency: --concurrency [N] default_value("8") "Number of user pages to fetch concurrently (html source only)")
+                            (@arg cookies: --cookies [FILE] "Load/save session cookies to this file between runs (html source only)")
+                            (@arg login: --login "Log in before scraping, to reach member-only contest data (html source only)")
+                            (@arg username: --username [NAME] requires[login] "Username to log in with")
+                            (@arg password: --password [PASSWORD] requires[login] "Password to log in with")
                             (@arg results: --results [FILE] "Write summary statistics to file")
                             (@arg writejson: --writejson [JSONFILE] conflicts_with[readjson results] "Don't print statistics, just write raw data to a json file (for later use with --readjson)")
-                            (@arg brief: --brief "Print only brief (top/honorable mention) summaries for each medium rather than full tables")
-                            (@arg html: --html "Print the output as a fragment of HTML")
+                            (@arg format: --format [FORMAT] possible_values(&["text", "html", "csv", "json", "msgpack"]) default_value("text") "Output format for the computed statistics")
+                            (@arg serve: --serve [ADDR] "Serve live statistics over HTTP from ADDR (e.g. 127.0.0.1:8080) instead of printing them once")
     ).get_matches();
 
+    let concurrency = value_t!(matches, "concurrency", usize).unwrap_or(8);
+    let cookie_path = matches.value_of("cookies").map(String::from);
+    let credentials = if matches.is_present("login") {
+        Some((
+            matches.value_of("username").expect("--login requires --username").to_string(),
+            matches.value_of("password").expect("--login requires --password").to_string(),
+        ))
+    } else {
+        None
+    };
+    // Built regardless of --readjson so --serve's refresh endpoint always
+    // has somewhere to re-fetch from.
+    let source = datasource_from_name(matches.value_of("source").unwrap(), concurrency, cookie_path, credentials);
+
     let users = if matches.is_present("readjson") {
         let jsonfile = File::open(matches.value_of("readjson").unwrap()).unwrap();
         read_json(&jsonfile)
     } else {
-        read_from_webpage()
+        source.fetch_users()
     }.unwrap();
 
     if matches.is_present("writejson") {
@@ -448,6 +1059,11 @@ fn main() {
         return;
     }
 
+    if let Some(addr) = matches.value_of("serve") {
+        serve(addr, users, source).unwrap();
+        return;
+    }
+
     let outfile = if matches.is_present("results") {
         let filename = matches.value_of("results").unwrap();
         Box::new(File::create(filename).unwrap()) as Box<Write>
@@ -455,7 +1071,9 @@ fn main() {
         Box::new(std::io::stdout()) as Box<Write>
     };
 
-    print_stats(outfile, &users, matches.is_present("brief"), matches.is_present("html"));
+    let tables = build_tables(&users);
+    let formatter = formatter_from_name(matches.value_of("format").unwrap());
+    formatter.format(outfile, &tables).unwrap();
 }
 
 #[cfg(test)]
@@ -470,11 +1088,27 @@ mod tests {
     use parse_userpage;
     use write_json;
     use read_json;
+    use DatedSeries;
+    use longest_streak;
+    use best_day;
+    use weekly_totals;
+    use most_active_week;
+    use epoch_ms_to_date;
+    use UserInfo;
+    use build_tables;
+    use std::collections::HashMap;
+    use std::borrow::Cow;
+    use std::sync::{Arc, Mutex};
+    use ResultTable;
+    use StatsFormatter;
+    use CsvFormatter;
+    use ranking_tables;
+    use SharedBuf;
 
     #[test]
     fn test_parse_mainpage() {
         let document = Document::from(include_str!("ranking.html"));
-        let users = parse_mainpage(document);
+        let users = parse_mainpage(document).unwrap();
         // Check that we parsed our sample document plausibly
         assert_eq!(users.len(), 28);
         assert_eq!(users[0], "801");
@@ -483,7 +1117,7 @@ mod tests {
     #[test]
     fn test_parse_userpage() {
         let document = Document::from(include_str!("userpage.html"));
-        let user = parse_userpage(document);
+        let user = parse_userpage(document).unwrap();
         println!{"{:#?}", user};
         assert_eq!(user.name, "shenmedemo");
         assert_eq!(user.totalpoints, 638.9);
@@ -491,14 +1125,14 @@ mod tests {
         let bookcount = user.countmap.get("Book").unwrap();
         assert!(bookcount == &91.0);
         assert_eq!(user.seriesmap.len(), 4);
-        assert_eq!(user.seriesmap.get("jp").unwrap().len(), 9);
+        assert_eq!(user.seriesmap.get("jp").unwrap().data.len(), 9);
     }
 
     #[test]
     fn test_write_read_json() {
         let document = Document::from(include_str!("userpage.html"));
         let mut users = Vec::new();
-        users.push(parse_userpage(document));
+        users.push(parse_userpage(document).unwrap());
         let mut tmpfile: File = tempfile::tempfile().unwrap();
         write_json(&tmpfile, &users).unwrap();
         tmpfile.seek(SeekFrom::Start(0)).unwrap();
@@ -511,4 +1145,94 @@ mod tests {
         assert_eq!(readusers.len(), users.len());
         assert_eq!(readusers[0].name, users[0].name);
     }
+
+    #[test]
+    fn test_longest_streak_ignores_trailing_zero_days() {
+        let series = DatedSeries {
+            point_start: 0,
+            point_interval: 86400000,
+            data: vec![1.0, 2.0, 0.0, 3.0, 4.0, 5.0, 0.0, 0.0],
+        };
+        // The trailing zero days (not yet reached by the contest) aren't
+        // part of any streak, so the longest run is the 3/4/5 one.
+        assert_eq!(longest_streak(&series), 3);
+    }
+
+    #[test]
+    fn test_best_day() {
+        let series = DatedSeries {
+            point_start: 1_000_000_000_000,
+            point_interval: 86400000,
+            data: vec![1.0, 5.0, 2.0],
+        };
+        assert_eq!(best_day(&series), Some((1_000_000_000_000 + 86400000, 5.0)));
+    }
+
+    #[test]
+    fn test_weekly_totals_and_most_active_week() {
+        let series = DatedSeries {
+            point_start: 0,
+            point_interval: 86400000,
+            // Week 0 (days 0-6) totals 7.0; week 1 (days 7-8) totals 10.0.
+            data: vec![1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 10.0, 0.0],
+        };
+        assert_eq!(weekly_totals(&series), vec![(0, 7.0), (7 * 86400000, 10.0)]);
+        assert_eq!(most_active_week(&series), Some((7 * 86400000, 10.0)));
+    }
+
+    #[test]
+    fn test_epoch_ms_to_date() {
+        assert_eq!(epoch_ms_to_date(0), "1970-01-01");
+        assert_eq!(epoch_ms_to_date(86400000), "1970-01-02");
+    }
+
+    #[test]
+    fn test_build_tables_dedupes_single_language_series() {
+        // A single-language user has their one real language's series
+        // duplicated under "Overall" too (see parse_userpage); the
+        // streak/best-day/most-active-week tables key off "Overall" only
+        // so such a user isn't double-counted.
+        let series = DatedSeries {
+            point_start: 0,
+            point_interval: 86400000,
+            data: vec![1.0, 1.0, 1.0],
+        };
+        let mut seriesmap = HashMap::new();
+        seriesmap.insert(String::from("Overall"), series.clone());
+        seriesmap.insert(String::from("jp"), series);
+        let mut countmap = HashMap::new();
+        countmap.insert(String::from("Book"), 3.0);
+        let user = UserInfo { name: String::from("alice"), countmap, seriesmap, totalpoints: 3.0 };
+        let users = vec![user];
+
+        let tables = build_tables(&users);
+        let streak = tables.iter().find(|(title, _)| title.as_str() == "Longest streak").unwrap();
+        assert_eq!(streak.1[0].1, 3.0);
+    }
+
+    #[test]
+    fn test_csv_formatter_row_shape() {
+        let tables: Vec<(String, ResultTable)> = vec![
+            (String::from("Overall rankings"), vec![(Cow::Borrowed("alice"), 12.5)]),
+        ];
+        let buf = Arc::new(Mutex::new(Vec::new()));
+        CsvFormatter.format(Box::new(SharedBuf(buf.clone())), &tables).unwrap();
+        let csv = String::from_utf8(Arc::try_unwrap(buf).unwrap().into_inner().unwrap()).unwrap();
+        assert_eq!(csv, "table,rank,name,value\nOverall rankings,1,alice,12.5\n");
+    }
+
+    #[test]
+    fn test_ranking_tables_json_shape() {
+        let tables: Vec<(String, ResultTable)> = vec![
+            (String::from("Overall rankings"),
+             vec![(Cow::Borrowed("alice"), 12.5), (Cow::Borrowed("bob"), 9.0)]),
+        ];
+        let ranking = ranking_tables(&tables);
+        assert_eq!(ranking.len(), 1);
+        assert_eq!(ranking[0].title, "Overall rankings");
+        assert_eq!(ranking[0].entries.len(), 2);
+        assert_eq!(ranking[0].entries[0].rank, 1);
+        assert_eq!(ranking[0].entries[0].name, "alice");
+        assert_eq!(ranking[0].entries[0].value, 12.5);
+    }
 }